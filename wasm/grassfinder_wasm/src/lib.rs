@@ -1,270 +1,1334 @@
-use wasm_bindgen::prelude::*;
-use js_sys::Int32Array;
-
-#[inline(always)]
-fn packed_offset_12bit(x: i32, y: i32, z: i32, post1_12: bool) -> u16 {
-    // Vanilla:
-    // long l = (long)(x * 3129871) ^ ((long)z * 116129781L) ^ (long)y;
-    // l = l*l*42317861L + l*11L;
-    // return (l >>> 16) & 0xFFF;
-    let yy = if post1_12 { 0 } else { y };
-
-    // x*3129871 happens as i32 multiply (wrap) then cast to long in vanilla expression
-    let x_term = (x.wrapping_mul(3_129_871) as i64);
-
-    // z term is long multiply
-    let z_term = (z as i64).wrapping_mul(116_129_781i64);
-
-    let mut l: i64 = x_term ^ z_term ^ (yy as i64);
-
-    l = l
-        .wrapping_mul(l)
-        .wrapping_mul(42_317_861i64)
-        .wrapping_add(l.wrapping_mul(11i64));
-
-    let u = l as u64;
-    ((u >> 16) & 0xFFF) as u16
-}
-
-#[inline(always)]
-fn axis_nibble(v: u16, axis: usize) -> u8 {
-    ((v >> (axis * 4)) & 0xF) as u8
-}
-
-#[inline(always)]
-fn dripstone_nibble_matches(expected: u8, predicted: u8) -> bool {
-    if expected <= 3 {
-        predicted <= 3
-    } else if expected >= 12 {
-        predicted >= 12
-    } else {
-        predicted == expected
-    }
-}
-
-#[inline(always)]
-fn dripstone_nibble_distance(expected: u8, predicted: u8) -> i32 {
-    if expected <= 3 {
-        if predicted <= 3 { 0 } else { (predicted - 3) as i32 }
-    } else if expected >= 12 {
-        if predicted >= 12 { 0 } else { (12 - predicted) as i32 }
-    } else {
-        (predicted as i32 - expected as i32).abs()
-    }
-}
-
-/// Strict scan: returns Int32Array [x,y,z, x,y,z, ...]
-#[wasm_bindgen]
-pub fn scan_strict_box(
-    rel_dx: &[i32],
-    rel_dy: &[i32],
-    rel_dz: &[i32],
-    rel_packed: &[u16],
-    rel_mask: &[u16],
-    rel_drip: &[u8],
-    post1_12_any_y: bool,
-    x0: i32, x1: i32,
-    y0: i32, y1: i32,
-    z0: i32, z1: i32,
-    max_matches: u32,
-) -> Result<Int32Array, JsValue> {
-    let n = rel_dx.len();
-    if rel_dy.len() != n || rel_dz.len() != n || rel_packed.len() != n || rel_mask.len() != n || rel_drip.len() != n {
-        return Err(JsValue::from_str("Input arrays must have the same length."));
-    }
-    if x0 > x1 || y0 > y1 || z0 > z1 {
-        return Err(JsValue::from_str("Invalid bounds (min > max)."));
-    }
-
-    let mut out: Vec<i32> = Vec::with_capacity((max_matches as usize).saturating_mul(3));
-
-    if post1_12_any_y {
-        let y = y0;
-        for z in z0..=z1 {
-            for x in x0..=x1 {
-                let mut ok = true;
-
-                for i in 0..n {
-                    let ax = x.wrapping_add(rel_dx[i]);
-                    let ay = y.wrapping_add(rel_dy[i]);
-                    let az = z.wrapping_add(rel_dz[i]);
-
-                    let pred = packed_offset_12bit(ax, ay, az, true);
-                    let mask = rel_mask[i];
-                    let exp  = rel_packed[i];
-
-                    if rel_drip[i] == 0 {
-                        if (pred & mask) != exp { ok = false; break; }
-                    } else {
-                        for axis in 0..3 {
-                            let nib_mask = ((mask >> (axis * 4)) & 0xF) as u16;
-                            if nib_mask == 0 { continue; }
-
-                            let pn = axis_nibble(pred, axis);
-                            let en = axis_nibble(exp, axis);
-
-                            if axis == 1 {
-                                if pn != en { ok = false; break; }
-                            } else {
-                                if !dripstone_nibble_matches(en, pn) { ok = false; break; }
-                            }
-                        }
-                        if !ok { break; }
-                    }
-                }
-
-                if ok {
-                    out.push(x); out.push(y); out.push(z);
-                    if (out.len() / 3) as u32 >= max_matches {
-                        return Ok(Int32Array::from(out.as_slice()));
-                    }
-                }
-            }
-        }
-    } else {
-        for y in y0..=y1 {
-            for z in z0..=z1 {
-                for x in x0..=x1 {
-                    let mut ok = true;
-
-                    for i in 0..n {
-                        let ax = x.wrapping_add(rel_dx[i]);
-                        let ay = y.wrapping_add(rel_dy[i]);
-                        let az = z.wrapping_add(rel_dz[i]);
-
-                        let pred = packed_offset_12bit(ax, ay, az, false);
-                        let mask = rel_mask[i];
-                        let exp  = rel_packed[i];
-
-                        if rel_drip[i] == 0 {
-                            if (pred & mask) != exp { ok = false; break; }
-                        } else {
-                            for axis in 0..3 {
-                                let nib_mask = ((mask >> (axis * 4)) & 0xF) as u16;
-                                if nib_mask == 0 { continue; }
-
-                                let pn = axis_nibble(pred, axis);
-                                let en = axis_nibble(exp, axis);
-
-                                if axis == 1 {
-                                    if pn != en { ok = false; break; }
-                                } else {
-                                    if !dripstone_nibble_matches(en, pn) { ok = false; break; }
-                                }
-                            }
-                            if !ok { break; }
-                        }
-                    }
-
-                    if ok {
-                        out.push(x); out.push(y); out.push(z);
-                        if (out.len() / 3) as u32 >= max_matches {
-                            return Ok(Int32Array::from(out.as_slice()));
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    Ok(Int32Array::from(out.as_slice()))
-}
-
-/// Scored scan: returns Int32Array [x,y,z,score, x,y,z,score, ...]
-#[wasm_bindgen]
-pub fn scan_scored_box(
-    rel_dx: &[i32],
-    rel_dy: &[i32],
-    rel_dz: &[i32],
-    rel_packed: &[u16],
-    rel_mask: &[u16],
-    rel_drip: &[u8],
-    post1_12_any_y: bool,
-    x0: i32, x1: i32,
-    y0: i32, y1: i32,
-    z0: i32, z1: i32,
-    max_matches: u32,
-    tol: u8,
-    max_score: i32,
-) -> Result<Int32Array, JsValue> {
-    let n = rel_dx.len();
-    if rel_dy.len() != n || rel_dz.len() != n || rel_packed.len() != n || rel_mask.len() != n || rel_drip.len() != n {
-        return Err(JsValue::from_str("Input arrays must have the same length."));
-    }
-    if x0 > x1 || y0 > y1 || z0 > z1 {
-        return Err(JsValue::from_str("Invalid bounds (min > max)."));
-    }
-
-    let tol_i = tol as i32;
-    let mut out: Vec<i32> = Vec::with_capacity((max_matches as usize).saturating_mul(4));
-
-    let mut check_candidate = |x: i32, y: i32, z: i32, post1_12: bool| -> Option<i32> {
-        let mut score: i32 = 0;
-
-        for i in 0..n {
-            let ax = x.wrapping_add(rel_dx[i]);
-            let ay = y.wrapping_add(rel_dy[i]);
-            let az = z.wrapping_add(rel_dz[i]);
-
-            let pred = packed_offset_12bit(ax, ay, az, post1_12);
-            let exp  = rel_packed[i];
-            let mask = rel_mask[i];
-            let drip = rel_drip[i] != 0;
-
-            for axis in 0..3 {
-                let nib_mask = ((mask >> (axis * 4)) & 0xF) as u16;
-                if nib_mask == 0 { continue; }
-
-                let pn = axis_nibble(pred, axis);
-                let en = axis_nibble(exp, axis);
-
-                let d = if drip && axis != 1 {
-                    dripstone_nibble_distance(en, pn)
-                } else {
-                    (pn as i32 - en as i32).abs()
-                };
-
-                if d <= tol_i {
-                    score += d;
-                } else {
-                    score += d * d;
-                }
-
-                if score > max_score {
-                    return None;
-                }
-            }
-        }
-
-        Some(score)
-    };
-
-    if post1_12_any_y {
-        let y = y0;
-        for z in z0..=z1 {
-            for x in x0..=x1 {
-                if let Some(s) = check_candidate(x, y, z, true) {
-                    out.push(x); out.push(y); out.push(z); out.push(s);
-                    if (out.len() / 4) as u32 >= max_matches {
-                        return Ok(Int32Array::from(out.as_slice()));
-                    }
-                }
-            }
-        }
-    } else {
-        for y in y0..=y1 {
-            for z in z0..=z1 {
-                for x in x0..=x1 {
-                    if let Some(s) = check_candidate(x, y, z, false) {
-                        out.push(x); out.push(y); out.push(z); out.push(s);
-                        if (out.len() / 4) as u32 >= max_matches {
-                            return Ok(Int32Array::from(out.as_slice()));
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    Ok(Int32Array::from(out.as_slice()))
-}
+// The scanning entry points take several parallel `rel_*` arrays plus box
+// bounds, so their arg count is inherent to the API shape, not a smell.
+#![allow(clippy::too_many_arguments)]
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use wasm_bindgen::prelude::*;
+use js_sys::{Int32Array, Uint16Array, Uint8Array};
+
+#[inline(always)]
+fn packed_offset_12bit(x: i32, y: i32, z: i32, post1_12: bool) -> u16 {
+    // Vanilla:
+    // long l = (long)(x * 3129871) ^ ((long)z * 116129781L) ^ (long)y;
+    // l = l*l*42317861L + l*11L;
+    // return (l >>> 16) & 0xFFF;
+    let yy = if post1_12 { 0 } else { y };
+
+    // x*3129871 happens as i32 multiply (wrap) then cast to long in vanilla expression
+    let x_term = (x.wrapping_mul(3_129_871) as i64);
+
+    // z term is long multiply
+    let z_term = (z as i64).wrapping_mul(116_129_781i64);
+
+    let mut l: i64 = x_term ^ z_term ^ (yy as i64);
+
+    l = l
+        .wrapping_mul(l)
+        .wrapping_mul(42_317_861i64)
+        .wrapping_add(l.wrapping_mul(11i64));
+
+    let u = l as u64;
+    ((u >> 16) & 0xFFF) as u16
+}
+
+/// Evaluates `packed_offset_12bit` for four adjacent `x` values (`x0..x0+3`)
+/// at a fixed `(y, z)`, reproducing the scalar wrapping arithmetic bit-for-bit.
+///
+/// On `wasm32` with `simd128` enabled this runs as a single SIMD pass over
+/// two `i64x2` vectors; everywhere else it falls back to four scalar calls.
+/// `y`/`z` are lane-invariant for a fixed relative offset, so only the `x`
+/// term needs to vary per lane.
+///
+/// `simd128` is not on by default for `wasm32-unknown-unknown` — this arm
+/// is only reachable because `.cargo/config.toml` sets
+/// `target-feature=+simd128` for that target.
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+#[inline(always)]
+fn packed_offset_12bit_x4(x0: i32, y: i32, z: i32, post1_12: bool) -> [u16; 4] {
+    use core::arch::wasm32::*;
+
+    let yy = if post1_12 { 0 } else { y };
+    let const_term = (z as i64).wrapping_mul(116_129_781i64) ^ (yy as i64);
+    let const_v = i64x2_splat(const_term);
+
+    let xs = i32x4(x0, x0.wrapping_add(1), x0.wrapping_add(2), x0.wrapping_add(3));
+    let x_mul = i32x4_mul(xs, i32x4_splat(3_129_871));
+
+    let l_lo = v128_xor(i64x2_extend_low_i32x4(x_mul), const_v);
+    let l_hi = v128_xor(i64x2_extend_high_i32x4(x_mul), const_v);
+
+    let t_lo = i64x2_add(
+        i64x2_mul(i64x2_mul(l_lo, l_lo), i64x2_splat(42_317_861)),
+        i64x2_mul(l_lo, i64x2_splat(11)),
+    );
+    let t_hi = i64x2_add(
+        i64x2_mul(i64x2_mul(l_hi, l_hi), i64x2_splat(42_317_861)),
+        i64x2_mul(l_hi, i64x2_splat(11)),
+    );
+
+    let m_lo = v128_and(u64x2_shr(t_lo, 16), i64x2_splat(0xFFF));
+    let m_hi = v128_and(u64x2_shr(t_hi, 16), i64x2_splat(0xFFF));
+
+    [
+        i64x2_extract_lane::<0>(m_lo) as u16,
+        i64x2_extract_lane::<1>(m_lo) as u16,
+        i64x2_extract_lane::<0>(m_hi) as u16,
+        i64x2_extract_lane::<1>(m_hi) as u16,
+    ]
+}
+
+/// Scalar fallback for [`packed_offset_12bit_x4`] on targets without `simd128`.
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+#[inline(always)]
+fn packed_offset_12bit_x4(x0: i32, y: i32, z: i32, post1_12: bool) -> [u16; 4] {
+    [
+        packed_offset_12bit(x0, y, z, post1_12),
+        packed_offset_12bit(x0.wrapping_add(1), y, z, post1_12),
+        packed_offset_12bit(x0.wrapping_add(2), y, z, post1_12),
+        packed_offset_12bit(x0.wrapping_add(3), y, z, post1_12),
+    ]
+}
+
+#[inline(always)]
+fn axis_nibble(v: u16, axis: usize) -> u8 {
+    ((v >> (axis * 4)) & 0xF) as u8
+}
+
+#[inline(always)]
+fn dripstone_nibble_matches(expected: u8, predicted: u8) -> bool {
+    if expected <= 3 {
+        predicted <= 3
+    } else if expected >= 12 {
+        predicted >= 12
+    } else {
+        predicted == expected
+    }
+}
+
+#[inline(always)]
+fn dripstone_nibble_distance(expected: u8, predicted: u8) -> i32 {
+    if expected <= 3 {
+        if predicted <= 3 { 0 } else { (predicted - 3) as i32 }
+    } else if expected >= 12 {
+        if predicted >= 12 { 0 } else { (12 - predicted) as i32 }
+    } else {
+        (predicted as i32 - expected as i32).abs()
+    }
+}
+
+#[inline(always)]
+fn strict_offset_matches(pred: u16, exp: u16, mask: u16, drip: bool) -> bool {
+    if !drip {
+        return (pred & mask) == exp;
+    }
+
+    for axis in 0..3 {
+        let nib_mask = (mask >> (axis * 4)) & 0xF;
+        if nib_mask == 0 { continue; }
+
+        let pn = axis_nibble(pred, axis);
+        let en = axis_nibble(exp, axis);
+
+        let matches = if axis == 1 {
+            pn == en
+        } else {
+            dripstone_nibble_matches(en, pn)
+        };
+        if !matches { return false; }
+    }
+    true
+}
+
+/// Scans one `(y, z)` row in chunks of up to 4 adjacent `x` candidates,
+/// pushing matches into `out`. Returns `true` once `max_matches` is reached.
+fn scan_strict_row(
+    rel_dx: &[i32],
+    rel_dy: &[i32],
+    rel_dz: &[i32],
+    rel_packed: &[u16],
+    rel_mask: &[u16],
+    rel_drip: &[u8],
+    y: i32, z: i32,
+    x0: i32, x1: i32,
+    post1_12: bool,
+    max_matches: u32,
+    out: &mut Vec<i32>,
+) -> bool {
+    let n = rel_dx.len();
+    let mut x = x0;
+
+    loop {
+        if x > x1 { break; }
+        let remaining = (x1 as i64) - (x as i64) + 1;
+        let lanes = remaining.min(4) as usize;
+
+        let mut ok = [true; 4];
+
+        for i in 0..n {
+            let ay = y.wrapping_add(rel_dy[i]);
+            let az = z.wrapping_add(rel_dz[i]);
+            let base_x = x.wrapping_add(rel_dx[i]);
+
+            let pred = packed_offset_12bit_x4(base_x, ay, az, post1_12);
+            let mask = rel_mask[i];
+            let exp = rel_packed[i];
+            let drip = rel_drip[i] != 0;
+
+            let mut any_live = false;
+            for (live, &p) in ok.iter_mut().zip(pred.iter()).take(lanes) {
+                if !*live { continue; }
+                if strict_offset_matches(p, exp, mask, drip) {
+                    any_live = true;
+                } else {
+                    *live = false;
+                }
+            }
+            if !any_live { break; }
+        }
+
+        for (lane, &is_match) in ok.iter().enumerate().take(lanes) {
+            if is_match {
+                let cx = x.wrapping_add(lane as i32);
+                out.push(cx); out.push(y); out.push(z);
+                if (out.len() / 3) as u32 >= max_matches {
+                    return true;
+                }
+            }
+        }
+
+        if lanes < 4 { break; }
+        match x.checked_add(4) {
+            Some(nx) => x = nx,
+            None => break,
+        }
+    }
+
+    false
+}
+
+/// Strict scan: returns Int32Array [x,y,z, x,y,z, ...]
+#[wasm_bindgen]
+pub fn scan_strict_box(
+    rel_dx: &[i32],
+    rel_dy: &[i32],
+    rel_dz: &[i32],
+    rel_packed: &[u16],
+    rel_mask: &[u16],
+    rel_drip: &[u8],
+    post1_12_any_y: bool,
+    x0: i32, x1: i32,
+    y0: i32, y1: i32,
+    z0: i32, z1: i32,
+    max_matches: u32,
+) -> Result<Int32Array, JsValue> {
+    let n = rel_dx.len();
+    if rel_dy.len() != n || rel_dz.len() != n || rel_packed.len() != n || rel_mask.len() != n || rel_drip.len() != n {
+        return Err(JsValue::from_str("Input arrays must have the same length."));
+    }
+    if x0 > x1 || y0 > y1 || z0 > z1 {
+        return Err(JsValue::from_str("Invalid bounds (min > max)."));
+    }
+
+    let mut out: Vec<i32> = Vec::with_capacity((max_matches as usize).saturating_mul(3));
+
+    if post1_12_any_y {
+        let y = y0;
+        for z in z0..=z1 {
+            if scan_strict_row(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, y, z, x0, x1, true, max_matches, &mut out) {
+                return Ok(Int32Array::from(out.as_slice()));
+            }
+        }
+    } else {
+        for y in y0..=y1 {
+            for z in z0..=z1 {
+                if scan_strict_row(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, y, z, x0, x1, false, max_matches, &mut out) {
+                    return Ok(Int32Array::from(out.as_slice()));
+                }
+            }
+        }
+    }
+
+    Ok(Int32Array::from(out.as_slice()))
+}
+
+#[inline(always)]
+fn scored_offset_distance(pred: u16, exp: u16, mask: u16, drip: bool, tol_i: i32) -> i32 {
+    let mut score = 0;
+
+    for axis in 0..3 {
+        let nib_mask = (mask >> (axis * 4)) & 0xF;
+        if nib_mask == 0 { continue; }
+
+        let pn = axis_nibble(pred, axis);
+        let en = axis_nibble(exp, axis);
+
+        let d = if drip && axis != 1 {
+            dripstone_nibble_distance(en, pn)
+        } else {
+            (pn as i32 - en as i32).abs()
+        };
+
+        score += if d <= tol_i { d } else { d * d };
+    }
+
+    score
+}
+
+/// Scans one `(y, z)` row in chunks of up to 4 adjacent `x` candidates,
+/// pushing `[x,y,z,score]` into `out`. Returns `true` once `max_matches`
+/// is reached. A lane's running score is abandoned (never emitted) as
+/// soon as it exceeds `max_score`, mirroring the scalar early-return.
+fn scan_scored_row(
+    rel_dx: &[i32],
+    rel_dy: &[i32],
+    rel_dz: &[i32],
+    rel_packed: &[u16],
+    rel_mask: &[u16],
+    rel_drip: &[u8],
+    y: i32, z: i32,
+    x0: i32, x1: i32,
+    post1_12: bool,
+    tol_i: i32,
+    max_score: i32,
+    max_matches: u32,
+    out: &mut Vec<i32>,
+) -> bool {
+    let n = rel_dx.len();
+    let mut x = x0;
+
+    loop {
+        if x > x1 { break; }
+        let remaining = (x1 as i64) - (x as i64) + 1;
+        let lanes = remaining.min(4) as usize;
+
+        let mut score = [0i32; 4];
+        let mut alive = [true; 4];
+
+        for i in 0..n {
+            let ay = y.wrapping_add(rel_dy[i]);
+            let az = z.wrapping_add(rel_dz[i]);
+            let base_x = x.wrapping_add(rel_dx[i]);
+
+            let pred = packed_offset_12bit_x4(base_x, ay, az, post1_12);
+            let exp = rel_packed[i];
+            let mask = rel_mask[i];
+            let drip = rel_drip[i] != 0;
+
+            let mut any_alive = false;
+            for ((live, s), &p) in alive.iter_mut().zip(score.iter_mut()).zip(pred.iter()).take(lanes) {
+                if !*live { continue; }
+                *s += scored_offset_distance(p, exp, mask, drip, tol_i);
+                if *s > max_score {
+                    *live = false;
+                } else {
+                    any_alive = true;
+                }
+            }
+            if !any_alive { break; }
+        }
+
+        for (lane, (&is_alive, &s)) in alive.iter().zip(score.iter()).enumerate().take(lanes) {
+            if is_alive {
+                let cx = x.wrapping_add(lane as i32);
+                out.push(cx); out.push(y); out.push(z); out.push(s);
+                if (out.len() / 4) as u32 >= max_matches {
+                    return true;
+                }
+            }
+        }
+
+        if lanes < 4 { break; }
+        match x.checked_add(4) {
+            Some(nx) => x = nx,
+            None => break,
+        }
+    }
+
+    false
+}
+
+/// Scored scan: returns Int32Array [x,y,z,score, x,y,z,score, ...]
+#[wasm_bindgen]
+pub fn scan_scored_box(
+    rel_dx: &[i32],
+    rel_dy: &[i32],
+    rel_dz: &[i32],
+    rel_packed: &[u16],
+    rel_mask: &[u16],
+    rel_drip: &[u8],
+    post1_12_any_y: bool,
+    x0: i32, x1: i32,
+    y0: i32, y1: i32,
+    z0: i32, z1: i32,
+    max_matches: u32,
+    tol: u8,
+    max_score: i32,
+) -> Result<Int32Array, JsValue> {
+    let n = rel_dx.len();
+    if rel_dy.len() != n || rel_dz.len() != n || rel_packed.len() != n || rel_mask.len() != n || rel_drip.len() != n {
+        return Err(JsValue::from_str("Input arrays must have the same length."));
+    }
+    if x0 > x1 || y0 > y1 || z0 > z1 {
+        return Err(JsValue::from_str("Invalid bounds (min > max)."));
+    }
+
+    let tol_i = tol as i32;
+    let mut out: Vec<i32> = Vec::with_capacity((max_matches as usize).saturating_mul(4));
+
+    if post1_12_any_y {
+        let y = y0;
+        for z in z0..=z1 {
+            if scan_scored_row(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, y, z, x0, x1, true, tol_i, max_score, max_matches, &mut out) {
+                return Ok(Int32Array::from(out.as_slice()));
+            }
+        }
+    } else {
+        for y in y0..=y1 {
+            for z in z0..=z1 {
+                if scan_scored_row(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, y, z, x0, x1, false, tol_i, max_score, max_matches, &mut out) {
+                    return Ok(Int32Array::from(out.as_slice()));
+                }
+            }
+        }
+    }
+
+    Ok(Int32Array::from(out.as_slice()))
+}
+
+/// A scored candidate kept in the top-K heap used by [`scan_topk_box`].
+/// Ordered purely by `score`, so the heap's max is always the
+/// current worst of the K best candidates seen so far.
+#[derive(Clone, Copy)]
+struct ScoredCandidate {
+    score: i32,
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+impl PartialEq for ScoredCandidate {
+    fn eq(&self, other: &Self) -> bool { self.score == other.score }
+}
+impl Eq for ScoredCandidate {}
+impl PartialOrd for ScoredCandidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for ScoredCandidate {
+    fn cmp(&self, other: &Self) -> Ordering { self.score.cmp(&other.score) }
+}
+
+/// Scans one `(y, z)` row, pushing every candidate whose score beats the
+/// current worst-of-K into `heap`, evicting the previous worst once the
+/// heap is full. `cutoff` starts at `max_score` and tightens to the
+/// heap's max once it reaches capacity, so later cells bail out earlier.
+fn scan_topk_row(
+    rel_dx: &[i32],
+    rel_dy: &[i32],
+    rel_dz: &[i32],
+    rel_packed: &[u16],
+    rel_mask: &[u16],
+    rel_drip: &[u8],
+    y: i32, z: i32,
+    x0: i32, x1: i32,
+    post1_12: bool,
+    tol_i: i32,
+    max_score: i32,
+    k: u32,
+    heap: &mut BinaryHeap<ScoredCandidate>,
+) {
+    let n = rel_dx.len();
+
+    for x in x0..=x1 {
+        let cutoff = if (heap.len() as u32) < k {
+            max_score
+        } else {
+            match heap.peek() {
+                Some(worst) => worst.score,
+                None => max_score,
+            }
+        };
+
+        let mut score = 0;
+        let mut ok = true;
+
+        for i in 0..n {
+            let ax = x.wrapping_add(rel_dx[i]);
+            let ay = y.wrapping_add(rel_dy[i]);
+            let az = z.wrapping_add(rel_dz[i]);
+
+            let pred = packed_offset_12bit(ax, ay, az, post1_12);
+            let drip = rel_drip[i] != 0;
+            score += scored_offset_distance(pred, rel_packed[i], rel_mask[i], drip, tol_i);
+            if score > cutoff {
+                ok = false;
+                break;
+            }
+        }
+
+        if !ok { continue; }
+
+        if (heap.len() as u32) < k {
+            heap.push(ScoredCandidate { score, x, y, z });
+        } else if heap.peek().is_some_and(|worst| score < worst.score) {
+            heap.pop();
+            heap.push(ScoredCandidate { score, x, y, z });
+        }
+    }
+}
+
+/// Top-K scan: scans the whole box and keeps only the `k` lowest-scoring
+/// candidates, via a bounded max-heap, so the globally best matches are
+/// returned instead of whichever happen to come first in scan order.
+/// Returns Int32Array [x,y,z,score, ...] sorted by ascending score.
+#[wasm_bindgen]
+pub fn scan_topk_box(
+    rel_dx: &[i32],
+    rel_dy: &[i32],
+    rel_dz: &[i32],
+    rel_packed: &[u16],
+    rel_mask: &[u16],
+    rel_drip: &[u8],
+    post1_12_any_y: bool,
+    x0: i32, x1: i32,
+    y0: i32, y1: i32,
+    z0: i32, z1: i32,
+    k: u32,
+    tol: u8,
+    max_score: i32,
+) -> Result<Int32Array, JsValue> {
+    let n = rel_dx.len();
+    if rel_dy.len() != n || rel_dz.len() != n || rel_packed.len() != n || rel_mask.len() != n || rel_drip.len() != n {
+        return Err(JsValue::from_str("Input arrays must have the same length."));
+    }
+    if x0 > x1 || y0 > y1 || z0 > z1 {
+        return Err(JsValue::from_str("Invalid bounds (min > max)."));
+    }
+
+    let tol_i = tol as i32;
+    let mut heap: BinaryHeap<ScoredCandidate> = BinaryHeap::with_capacity((k as usize).saturating_add(1));
+
+    if post1_12_any_y {
+        let y = y0;
+        for z in z0..=z1 {
+            scan_topk_row(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, y, z, x0, x1, true, tol_i, max_score, k, &mut heap);
+        }
+    } else {
+        for y in y0..=y1 {
+            for z in z0..=z1 {
+                scan_topk_row(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, y, z, x0, x1, false, tol_i, max_score, k, &mut heap);
+            }
+        }
+    }
+
+    // BinaryHeap::into_sorted_vec is already ascending by Ord, i.e. ascending score.
+    let sorted: Vec<ScoredCandidate> = heap.into_sorted_vec();
+
+    let mut out: Vec<i32> = Vec::with_capacity(sorted.len() * 4);
+    for c in sorted {
+        out.push(c.x); out.push(c.y); out.push(c.z); out.push(c.score);
+    }
+
+    Ok(Int32Array::from(out.as_slice()))
+}
+
+/// Resumable scan over a box, suitable for driving from `requestAnimationFrame`
+/// so a large region doesn't block the browser in one synchronous call.
+///
+/// Holds the pattern arrays and a cursor `(x, y, z)` into the same nested
+/// `y/z/x` iteration order (or the single-`y` order when `post1_12_any_y`
+/// is set) used by [`scan_strict_box`] / [`scan_scored_box`], so calling
+/// `step` repeatedly until `is_done` returns the exact same match set as
+/// one synchronous call would.
+#[wasm_bindgen]
+pub struct ScanSession {
+    rel_dx: Vec<i32>,
+    rel_dy: Vec<i32>,
+    rel_dz: Vec<i32>,
+    rel_packed: Vec<u16>,
+    rel_mask: Vec<u16>,
+    rel_drip: Vec<u8>,
+    post1_12_any_y: bool,
+    x0: i32, x1: i32,
+    y1: i32,
+    z0: i32, z1: i32,
+    scored: bool,
+    tol_i: i32,
+    max_score: i32,
+    cur_x: i32,
+    cur_y: i32,
+    cur_z: i32,
+    done: bool,
+    cells_scanned: f64,
+    total_cells: f64,
+    pending: Vec<i32>,
+}
+
+#[wasm_bindgen]
+impl ScanSession {
+    /// Creates a strict-match session (see [`scan_strict_box`]).
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        rel_dx: Vec<i32>,
+        rel_dy: Vec<i32>,
+        rel_dz: Vec<i32>,
+        rel_packed: Vec<u16>,
+        rel_mask: Vec<u16>,
+        rel_drip: Vec<u8>,
+        post1_12_any_y: bool,
+        x0: i32, x1: i32,
+        y0: i32, y1: i32,
+        z0: i32, z1: i32,
+    ) -> Result<ScanSession, JsValue> {
+        Self::new_impl(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, post1_12_any_y, x0, x1, y0, y1, z0, z1, false, 0, 0)
+    }
+
+    /// Creates a scored session (see [`scan_scored_box`]); matches are
+    /// emitted as `[x,y,z,score]` instead of `[x,y,z]`.
+    pub fn new_scored(
+        rel_dx: Vec<i32>,
+        rel_dy: Vec<i32>,
+        rel_dz: Vec<i32>,
+        rel_packed: Vec<u16>,
+        rel_mask: Vec<u16>,
+        rel_drip: Vec<u8>,
+        post1_12_any_y: bool,
+        x0: i32, x1: i32,
+        y0: i32, y1: i32,
+        z0: i32, z1: i32,
+        tol: u8,
+        max_score: i32,
+    ) -> Result<ScanSession, JsValue> {
+        Self::new_impl(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, post1_12_any_y, x0, x1, y0, y1, z0, z1, true, tol as i32, max_score)
+    }
+
+    fn new_impl(
+        rel_dx: Vec<i32>,
+        rel_dy: Vec<i32>,
+        rel_dz: Vec<i32>,
+        rel_packed: Vec<u16>,
+        rel_mask: Vec<u16>,
+        rel_drip: Vec<u8>,
+        post1_12_any_y: bool,
+        x0: i32, x1: i32,
+        y0: i32, y1: i32,
+        z0: i32, z1: i32,
+        scored: bool,
+        tol_i: i32,
+        max_score: i32,
+    ) -> Result<ScanSession, JsValue> {
+        let n = rel_dx.len();
+        if rel_dy.len() != n || rel_dz.len() != n || rel_packed.len() != n || rel_mask.len() != n || rel_drip.len() != n {
+            return Err(JsValue::from_str("Input arrays must have the same length."));
+        }
+        if x0 > x1 || y0 > y1 || z0 > z1 {
+            return Err(JsValue::from_str("Invalid bounds (min > max)."));
+        }
+
+        let x_count = (x1 as i64 - x0 as i64 + 1) as f64;
+        let z_count = (z1 as i64 - z0 as i64 + 1) as f64;
+        let y_count = if post1_12_any_y { 1.0 } else { (y1 as i64 - y0 as i64 + 1) as f64 };
+        let total_cells = x_count * z_count * y_count;
+
+        Ok(ScanSession {
+            rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip,
+            post1_12_any_y,
+            x0, x1,
+            y1,
+            z0, z1,
+            scored,
+            tol_i,
+            max_score,
+            cur_x: x0,
+            cur_y: y0,
+            cur_z: z0,
+            done: false,
+            cells_scanned: 0.0,
+            total_cells,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Scans at most `max_cells` candidates and advances the cursor.
+    /// Matches found along the way are appended to the pending buffer;
+    /// drain them with [`ScanSession::take_results`].
+    pub fn step(&mut self, max_cells: u32) {
+        let mut budget = max_cells;
+
+        while budget > 0 && !self.done {
+            let (x, y, z) = (self.cur_x, self.cur_y, self.cur_z);
+
+            if self.scored {
+                if let Some(score) = self.score_candidate(x, y, z) {
+                    self.pending.push(x);
+                    self.pending.push(y);
+                    self.pending.push(z);
+                    self.pending.push(score);
+                }
+            } else if self.strict_matches(x, y, z) {
+                self.pending.push(x);
+                self.pending.push(y);
+                self.pending.push(z);
+            }
+
+            self.cells_scanned += 1.0;
+            budget -= 1;
+            self.advance();
+        }
+    }
+
+    /// Cells scanned so far divided by the total cell count (1.0 once done).
+    pub fn progress(&self) -> f64 {
+        if self.total_cells <= 0.0 { 1.0 } else { (self.cells_scanned / self.total_cells).min(1.0) }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Drains and returns the matches accumulated since the last call.
+    pub fn take_results(&mut self) -> Int32Array {
+        let arr = Int32Array::from(self.pending.as_slice());
+        self.pending.clear();
+        arr
+    }
+}
+
+impl ScanSession {
+    fn strict_matches(&self, x: i32, y: i32, z: i32) -> bool {
+        for i in 0..self.rel_dx.len() {
+            let ax = x.wrapping_add(self.rel_dx[i]);
+            let ay = y.wrapping_add(self.rel_dy[i]);
+            let az = z.wrapping_add(self.rel_dz[i]);
+
+            let pred = packed_offset_12bit(ax, ay, az, self.post1_12_any_y);
+            let drip = self.rel_drip[i] != 0;
+            if !strict_offset_matches(pred, self.rel_packed[i], self.rel_mask[i], drip) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn score_candidate(&self, x: i32, y: i32, z: i32) -> Option<i32> {
+        let mut score = 0;
+
+        for i in 0..self.rel_dx.len() {
+            let ax = x.wrapping_add(self.rel_dx[i]);
+            let ay = y.wrapping_add(self.rel_dy[i]);
+            let az = z.wrapping_add(self.rel_dz[i]);
+
+            let pred = packed_offset_12bit(ax, ay, az, self.post1_12_any_y);
+            let drip = self.rel_drip[i] != 0;
+            score += scored_offset_distance(pred, self.rel_packed[i], self.rel_mask[i], drip, self.tol_i);
+            if score > self.max_score {
+                return None;
+            }
+        }
+
+        Some(score)
+    }
+
+    /// Advances the cursor through the same nested `y/z/x` order the
+    /// one-shot scanners use (single `y` when `post1_12_any_y` is set).
+    fn advance(&mut self) {
+        // Boxes may legitimately extend to an axis extreme (e.g. x1 ==
+        // i32::MAX), so treat a `checked_add` overflow as "bound reached"
+        // rather than wrapping or panicking, mirroring how the one-shot
+        // scanners use `x.checked_add(4)` to step safely.
+        let x_done = match self.cur_x.checked_add(1) {
+            Some(next) if next <= self.x1 => {
+                self.cur_x = next;
+                false
+            }
+            _ => true,
+        };
+        if !x_done {
+            return;
+        }
+        self.cur_x = self.x0;
+
+        let z_done = match self.cur_z.checked_add(1) {
+            Some(next) if next <= self.z1 => {
+                self.cur_z = next;
+                false
+            }
+            _ => true,
+        };
+        if !z_done {
+            return;
+        }
+        self.cur_z = self.z0;
+
+        if self.post1_12_any_y {
+            self.done = true;
+            return;
+        }
+        match self.cur_y.checked_add(1) {
+            Some(next) if next <= self.y1 => self.cur_y = next,
+            _ => self.done = true,
+        }
+    }
+}
+
+/// The parallel `rel_*` arrays produced by [`parse_pattern_dump`], ready to
+/// hand straight to `scan_strict_box` / `scan_scored_box` / `ScanSession`.
+#[wasm_bindgen]
+pub struct ParsedPattern {
+    rel_dx: Vec<i32>,
+    rel_dy: Vec<i32>,
+    rel_dz: Vec<i32>,
+    rel_packed: Vec<u16>,
+    rel_mask: Vec<u16>,
+    rel_drip: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl ParsedPattern {
+    pub fn rel_dx(&self) -> Int32Array { Int32Array::from(self.rel_dx.as_slice()) }
+    pub fn rel_dy(&self) -> Int32Array { Int32Array::from(self.rel_dy.as_slice()) }
+    pub fn rel_dz(&self) -> Int32Array { Int32Array::from(self.rel_dz.as_slice()) }
+    pub fn rel_packed(&self) -> Uint16Array { Uint16Array::from(self.rel_packed.as_slice()) }
+    pub fn rel_mask(&self) -> Uint16Array { Uint16Array::from(self.rel_mask.as_slice()) }
+    pub fn rel_drip(&self) -> Uint8Array { Uint8Array::from(self.rel_drip.as_slice()) }
+
+    pub fn len(&self) -> usize { self.rel_dx.len() }
+    pub fn is_empty(&self) -> bool { self.rel_dx.is_empty() }
+}
+
+/// Parses a pasted block-state dump (one `dx dy dz n0=<spec> [n1=<spec>]
+/// [n2=<spec>]` line per relative offset) into the six parallel `rel_*`
+/// arrays the scanners expect. Blank lines and lines starting with `#` are
+/// ignored; a `#` after the fields is not treated as a comment.
+/// `spec` is `*` (unconstrained), an exact nibble `0..15`, `drip-down`/
+/// `drip-up` (dripstone category match), or `drip:<0..15>` (dripstone
+/// match); drip specs are invalid on `n1`. `rel_drip` is one flag per
+/// line, so once any axis of a line uses a drip spec, every `n0`/`n2`
+/// value of that line in `0..3` or `12..15` (plain or `drip:`) is matched
+/// by dripstone category rather than exactly; values `4..11` stay exact.
+/// On failure, every malformed
+/// line is collected into a single `Err` message (one `line N: ...` per
+/// line).
+#[wasm_bindgen]
+pub fn parse_pattern_dump(text: &str) -> Result<ParsedPattern, JsValue> {
+    let mut rel_dx = Vec::new();
+    let mut rel_dy = Vec::new();
+    let mut rel_dz = Vec::new();
+    let mut rel_packed = Vec::new();
+    let mut rel_mask = Vec::new();
+    let mut rel_drip = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+
+    for (line_no, raw_line) in text.lines().enumerate() {
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match parse_pattern_line(line) {
+            Ok((dx, dy, dz, packed, mask, drip)) => {
+                rel_dx.push(dx);
+                rel_dy.push(dy);
+                rel_dz.push(dz);
+                rel_packed.push(packed);
+                rel_mask.push(mask);
+                rel_drip.push(drip);
+            }
+            Err(msg) => errors.push(format!("line {}: {}", line_no + 1, msg)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(JsValue::from_str(&errors.join("\n")));
+    }
+
+    Ok(ParsedPattern { rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip })
+}
+
+/// Parses one non-blank, non-comment pattern line into
+/// `(dx, dy, dz, packed, mask, drip)`, or a human-readable error.
+fn parse_pattern_line(line: &str) -> Result<(i32, i32, i32, u16, u16, u8), String> {
+    let mut fields = line.split_whitespace();
+
+    let dx = fields.next().ok_or("missing dx")?
+        .parse::<i32>().map_err(|_| "dx is not a valid integer".to_string())?;
+    let dy = fields.next().ok_or("missing dy")?
+        .parse::<i32>().map_err(|_| "dy is not a valid integer".to_string())?;
+    let dz = fields.next().ok_or("missing dz")?
+        .parse::<i32>().map_err(|_| "dz is not a valid integer".to_string())?;
+
+    let mut packed: u16 = 0;
+    let mut mask: u16 = 0;
+    let mut drip = false;
+    // Tracked separately from `mask`: a `n<axis>=*` spec leaves `mask`
+    // untouched (it matches anything), so `mask` alone can't catch a
+    // duplicate like `n0=* n0=5`.
+    let mut seen: u8 = 0;
+
+    for field in fields {
+        let (axis_str, spec) = field.split_once('=')
+            .ok_or_else(|| format!("expected `n<axis>=<spec>`, got `{}`", field))?;
+
+        let axis: u32 = match axis_str {
+            "n0" => 0,
+            "n1" => 1,
+            "n2" => 2,
+            other => return Err(format!("unknown axis `{}` (expected n0, n1 or n2)", other)),
+        };
+
+        if seen & (1 << axis) != 0 {
+            return Err(format!("axis `{}` specified more than once", axis_str));
+        }
+        seen |= 1 << axis;
+
+        let (value, is_drip): (u16, bool) = if spec == "*" {
+            continue;
+        } else if spec == "drip-down" {
+            (0, true)
+        } else if spec == "drip-up" {
+            (15, true)
+        } else if let Some(rest) = spec.strip_prefix("drip:") {
+            let v: u16 = rest.parse().map_err(|_| format!("invalid drip value `{}`", rest))?;
+            if v > 15 {
+                return Err(format!("drip value `{}` out of range 0..15", v));
+            }
+            (v, true)
+        } else {
+            let v: u16 = spec.parse().map_err(|_| format!("invalid nibble value `{}`", spec))?;
+            if v > 15 {
+                return Err(format!("nibble value `{}` out of range 0..15", v));
+            }
+            (v, false)
+        };
+
+        if is_drip && axis == 1 {
+            return Err("drip states are not valid on n1 (always exact-matched)".to_string());
+        }
+
+        packed |= value << (axis * 4);
+        mask |= 0xF << (axis * 4);
+        if is_drip {
+            drip = true;
+        }
+    }
+
+    if mask == 0 {
+        return Err("at least one of n0/n1/n2 is required".to_string());
+    }
+
+    Ok((dx, dy, dz, packed, mask, if drip { 1 } else { 0 }))
+}
+
+#[inline(always)]
+fn zigzag_encode(v: i32) -> u32 {
+    ((v << 1) ^ (v >> 31)) as u32
+}
+
+#[inline(always)]
+fn zigzag_decode(v: u32) -> i32 {
+    ((v >> 1) as i32) ^ -((v & 1) as i32)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u32) {
+    loop {
+        let byte = (v & 0x7F) as u8;
+        v >>= 7;
+        if v == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u32, JsValue> {
+    let mut result: u32 = 0;
+    let mut shift: u32 = 0;
+
+    loop {
+        let byte = *bytes.get(*pos).ok_or_else(|| JsValue::from_str("Truncated varint in encoded results."))?;
+        *pos += 1;
+        result |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+        if shift >= 32 {
+            return Err(JsValue::from_str("Varint too long in encoded results."));
+        }
+    }
+}
+
+/// Compresses `[x,y,z, ...]` or `[x,y,z,score, ...]` scan results (`stride`
+/// fields per record, in scan order) into a compact byte buffer: each
+/// field is delta-from-previous-record, zig-zag varint encoded, so long
+/// runs of nearby matches cost only a byte or two each. Decode with
+/// [`decode_results`].
+#[wasm_bindgen]
+pub fn encode_results_delta(values: &[i32], stride: u32) -> Result<Vec<u8>, JsValue> {
+    if stride == 0 || stride > 255 {
+        return Err(JsValue::from_str("stride must be between 1 and 255."));
+    }
+    #[allow(clippy::manual_is_multiple_of)] // keep plain `%` for clarity without relying on a newer std API
+    if values.len() % (stride as usize) != 0 {
+        return Err(JsValue::from_str("values.len() must be a multiple of stride."));
+    }
+
+    let stride = stride as usize;
+    // Worst case (large alternating deltas) needs up to 5 varint bytes per
+    // value; most clustered-match inputs will use far less.
+    let mut out = Vec::with_capacity(1 + values.len() * 5);
+    out.push(stride as u8);
+
+    let mut prev = vec![0i32; stride];
+    for record in values.chunks_exact(stride) {
+        for (field, &v) in record.iter().enumerate() {
+            write_varint(&mut out, zigzag_encode(v.wrapping_sub(prev[field])));
+            prev[field] = v;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Expands a byte buffer produced by [`encode_results_delta`] back into a
+/// flat `Int32Array` of the original records.
+#[wasm_bindgen]
+pub fn decode_results(bytes: &[u8]) -> Result<Int32Array, JsValue> {
+    let stride = *bytes.first().ok_or_else(|| JsValue::from_str("Empty encoded results buffer."))? as usize;
+    if stride == 0 {
+        return Err(JsValue::from_str("Encoded stride must be nonzero."));
+    }
+
+    let mut pos = 1usize;
+    let mut prev = vec![0i32; stride];
+    let mut out = Vec::new();
+
+    while pos < bytes.len() {
+        for p in prev.iter_mut() {
+            let delta = zigzag_decode(read_varint(bytes, &mut pos)?);
+            *p = p.wrapping_add(delta);
+            out.push(*p);
+        }
+    }
+
+    Ok(Int32Array::from(out.as_slice()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic xorshift64 (13/7/17 shifts) with a xorshift64*-style
+    // output multiplier, so the sweep is reproducible without a `rand`
+    // dependency.
+    fn next_u64(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    // `packed_offset_12bit_x4` must agree lane-for-lane with four scalar
+    // `packed_offset_12bit` calls, including where `x0 + lane` wraps around
+    // `i32::MAX`/`i32::MIN`.
+    #[test]
+    fn x4_matches_scalar_across_random_and_edge_coordinates() {
+        let edge_cases = [
+            (i32::MAX - 2, 0, 0, false),
+            (i32::MAX, 0, 0, false),
+            (i32::MIN, 0, 0, true),
+            (i32::MIN + 1, i32::MAX, i32::MIN, true),
+            (0, 0, 0, false),
+            (-1, -1, -1, true),
+        ];
+        let mut state = 0x9E3779B97F4A7C15u64;
+        let random_cases = (0..256).map(|_| {
+            let x0 = next_u64(&mut state) as i32;
+            let y = next_u64(&mut state) as i32;
+            let z = next_u64(&mut state) as i32;
+            let post1_12 = next_u64(&mut state) & 1 == 0;
+            (x0, y, z, post1_12)
+        });
+
+        for (x0, y, z, post1_12) in edge_cases.into_iter().chain(random_cases) {
+            let vec4 = packed_offset_12bit_x4(x0, y, z, post1_12);
+            for (lane, &packed) in vec4.iter().enumerate() {
+                let expected = packed_offset_12bit(x0.wrapping_add(lane as i32), y, z, post1_12);
+                assert_eq!(
+                    packed, expected,
+                    "lane {lane} mismatch for x0={x0}, y={y}, z={z}, post1_12={post1_12}"
+                );
+            }
+        }
+    }
+
+    // This only exercises the scalar fallback arm of `packed_offset_12bit_x4`
+    // (the one compiled for any non-`wasm32`+`simd128` target, including the
+    // host target tests run on). The `simd128` intrinsic arm still needs a
+    // `wasm-bindgen-test` pass under an actual `wasm32-unknown-unknown` +
+    // `simd128` target to get the same coverage.
+
+    // A small, arbitrary two-offset pattern reused by the `ScanSession`
+    // cross-check tests below.
+    type RelArrays = (Vec<i32>, Vec<i32>, Vec<i32>, Vec<u16>, Vec<u16>, Vec<u8>);
+    fn sample_pattern() -> RelArrays {
+        (
+            vec![0, 1],
+            vec![0, 0],
+            vec![0, 1],
+            vec![packed_offset_12bit(5, 10, 5, false), packed_offset_12bit(6, 10, 6, false)],
+            vec![0xFFF, 0xFFF],
+            vec![0, 0],
+        )
+    }
+
+    // One-shot `scan_strict_box`/`scan_scored_box` build their Int32Array
+    // output through the `js_sys`/`wasm_bindgen` boundary, which aborts
+    // under plain `cargo test` on a non-wasm host. `scan_strict_row` /
+    // `scan_scored_row` hold the actual scan logic and only deal in plain
+    // slices/Vecs, so driving them directly (with a `max_matches` high
+    // enough to never early-return) reproduces exactly what the `#[wasm_bindgen]`
+    // wrappers would have collected.
+    fn one_shot_strict(
+        rel_dx: &[i32], rel_dy: &[i32], rel_dz: &[i32],
+        rel_packed: &[u16], rel_mask: &[u16], rel_drip: &[u8],
+        post1_12_any_y: bool,
+        x0: i32, x1: i32, y0: i32, y1: i32, z0: i32, z1: i32,
+    ) -> Vec<i32> {
+        let mut out = Vec::new();
+        if post1_12_any_y {
+            for z in z0..=z1 {
+                scan_strict_row(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, y0, z, x0, x1, true, u32::MAX, &mut out);
+            }
+        } else {
+            for y in y0..=y1 {
+                for z in z0..=z1 {
+                    scan_strict_row(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, y, z, x0, x1, false, u32::MAX, &mut out);
+                }
+            }
+        }
+        out
+    }
+
+    fn one_shot_scored(
+        rel_dx: &[i32], rel_dy: &[i32], rel_dz: &[i32],
+        rel_packed: &[u16], rel_mask: &[u16], rel_drip: &[u8],
+        post1_12_any_y: bool,
+        x0: i32, x1: i32, y0: i32, y1: i32, z0: i32, z1: i32,
+        tol_i: i32, max_score: i32,
+    ) -> Vec<i32> {
+        let mut out = Vec::new();
+        if post1_12_any_y {
+            for z in z0..=z1 {
+                scan_scored_row(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, y0, z, x0, x1, true, tol_i, max_score, u32::MAX, &mut out);
+            }
+        } else {
+            for y in y0..=y1 {
+                for z in z0..=z1 {
+                    scan_scored_row(rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip, y, z, x0, x1, false, tol_i, max_score, u32::MAX, &mut out);
+                }
+            }
+        }
+        out
+    }
+
+    // Steps `session` to completion in small increments (to exercise the
+    // resumable cursor rather than one giant `step`) and returns the
+    // concatenated `pending` buffer, reading the field directly instead of
+    // through `take_results` (which builds an `Int32Array` and would abort
+    // off the `wasm_bindgen` boundary under plain `cargo test`).
+    fn drain_session(session: &mut ScanSession) -> Vec<i32> {
+        let mut all = Vec::new();
+        while !session.is_done() {
+            session.step(3);
+            all.append(&mut session.pending);
+        }
+        all
+    }
+
+    #[test]
+    fn scan_session_strict_matches_one_shot_scan() {
+        let (rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip) = sample_pattern();
+        let (x0, x1, y0, y1, z0, z1) = (0, 9, 8, 12, 0, 9);
+
+        let expected = one_shot_strict(&rel_dx, &rel_dy, &rel_dz, &rel_packed, &rel_mask, &rel_drip, false, x0, x1, y0, y1, z0, z1);
+
+        let mut session = ScanSession::new_impl(
+            rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip,
+            false, x0, x1, y0, y1, z0, z1, false, 0, 0,
+        )
+        .unwrap();
+        let actual = drain_session(&mut session);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn scan_session_strict_matches_one_shot_scan_with_post1_12_any_y() {
+        let (rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip) = sample_pattern();
+        let (x0, x1, y0, y1, z0, z1) = (0, 9, 10, 10, 0, 9);
+
+        let expected = one_shot_strict(&rel_dx, &rel_dy, &rel_dz, &rel_packed, &rel_mask, &rel_drip, true, x0, x1, y0, y1, z0, z1);
+
+        let mut session = ScanSession::new_impl(
+            rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip,
+            true, x0, x1, y0, y1, z0, z1, false, 0, 0,
+        )
+        .unwrap();
+        let actual = drain_session(&mut session);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn scan_session_scored_matches_one_shot_scan() {
+        let (rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip) = sample_pattern();
+        let (x0, x1, y0, y1, z0, z1) = (0, 9, 8, 12, 0, 9);
+        let (tol_i, max_score) = (1, 50);
+
+        let expected = one_shot_scored(&rel_dx, &rel_dy, &rel_dz, &rel_packed, &rel_mask, &rel_drip, false, x0, x1, y0, y1, z0, z1, tol_i, max_score);
+
+        let mut session = ScanSession::new_impl(
+            rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip,
+            false, x0, x1, y0, y1, z0, z1, true, tol_i, max_score,
+        )
+        .unwrap();
+        let actual = drain_session(&mut session);
+
+        assert_eq!(actual, expected);
+    }
+
+    // Brute-force reference for `scan_topk_row`/`scan_topk_box`: scores
+    // every cell in the box with no early cutoff, then sorts ascending by
+    // score and keeps the best `k`. `scan_topk_box`'s tightening-cutoff
+    // heap is an optimization over exactly this.
+    fn brute_force_topk(
+        rel_dx: &[i32], rel_dy: &[i32], rel_dz: &[i32],
+        rel_packed: &[u16], rel_mask: &[u16], rel_drip: &[u8],
+        x0: i32, x1: i32, y0: i32, y1: i32, z0: i32, z1: i32,
+        post1_12: bool, tol_i: i32, max_score: i32, k: usize,
+    ) -> Vec<(i32, i32, i32, i32)> {
+        let mut all = Vec::new();
+        for y in y0..=y1 {
+            for z in z0..=z1 {
+                for x in x0..=x1 {
+                    let mut score = 0;
+                    for i in 0..rel_dx.len() {
+                        let ax = x.wrapping_add(rel_dx[i]);
+                        let ay = y.wrapping_add(rel_dy[i]);
+                        let az = z.wrapping_add(rel_dz[i]);
+                        let pred = packed_offset_12bit(ax, ay, az, post1_12);
+                        let drip = rel_drip[i] != 0;
+                        score += scored_offset_distance(pred, rel_packed[i], rel_mask[i], drip, tol_i);
+                    }
+                    if score <= max_score {
+                        all.push((score, x, y, z));
+                    }
+                }
+            }
+        }
+        all.sort_by_key(|&(score, ..)| score);
+        all.truncate(k);
+        all.into_iter().map(|(score, x, y, z)| (x, y, z, score)).collect()
+    }
+
+    #[test]
+    fn scan_topk_box_matches_brute_force_lowest_k() {
+        let (rel_dx, rel_dy, rel_dz, rel_packed, rel_mask, rel_drip) = sample_pattern();
+        let (x0, x1, y0, y1, z0, z1) = (0, 9, 8, 12, 0, 9);
+        let (tol_i, max_score, k) = (1, 500, 5u32);
+
+        let expected = brute_force_topk(
+            &rel_dx, &rel_dy, &rel_dz, &rel_packed, &rel_mask, &rel_drip,
+            x0, x1, y0, y1, z0, z1, false, tol_i, max_score, k as usize,
+        );
+
+        let mut heap: BinaryHeap<ScoredCandidate> = BinaryHeap::with_capacity((k as usize) + 1);
+        for y in y0..=y1 {
+            for z in z0..=z1 {
+                scan_topk_row(&rel_dx, &rel_dy, &rel_dz, &rel_packed, &rel_mask, &rel_drip, y, z, x0, x1, false, tol_i, max_score, k, &mut heap);
+            }
+        }
+        let actual: Vec<(i32, i32, i32, i32)> = heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|c| (c.x, c.y, c.z, c.score))
+            .collect();
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn parse_pattern_line_parses_a_well_formed_line() {
+        // n1 is always exact-matched (drip specs are rejected there), so
+        // the drip spec goes on n0 and n1 gets a plain nibble value.
+        let (dx, dy, dz, packed, mask, drip) =
+            parse_pattern_line("1 -2 3 n0=drip-down n1=7 n2=*").unwrap();
+        assert_eq!((dx, dy, dz), (1, -2, 3));
+        assert_eq!(packed, 7 << 4);
+        assert_eq!(mask, 0xF | (0xF << 4));
+        assert_eq!(drip, 1);
+    }
+
+    #[test]
+    fn parse_pattern_line_rejects_duplicate_axis_even_after_a_star() {
+        let err = parse_pattern_line("0 0 0 n0=* n0=5").unwrap_err();
+        assert!(err.contains("more than once"), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn parse_pattern_line_rejects_plain_duplicate_axis() {
+        let err = parse_pattern_line("0 0 0 n0=5 n0=6").unwrap_err();
+        assert!(err.contains("more than once"), "unexpected error: {err}");
+    }
+
+    #[test]
+    fn parse_pattern_line_rejects_drip_on_n1() {
+        let err = parse_pattern_line("0 0 0 n1=drip-down").unwrap_err();
+        assert!(err.contains("n1"), "unexpected error: {err}");
+    }
+
+    // Mirrors decode_results's delta/varint/zigzag logic exactly, but
+    // collects into a plain Vec<i32> instead of building an Int32Array,
+    // since constructing one aborts under plain `cargo test` on a
+    // non-wasm host.
+    fn decode_plain(bytes: &[u8]) -> Vec<i32> {
+        let stride = bytes[0] as usize;
+        let mut pos = 1usize;
+        let mut prev = vec![0i32; stride];
+        let mut out = Vec::new();
+        while pos < bytes.len() {
+            for p in prev.iter_mut() {
+                let delta = zigzag_decode(read_varint(bytes, &mut pos).unwrap());
+                *p = p.wrapping_add(delta);
+                out.push(*p);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn encode_decode_round_trips_including_extremes() {
+        let stride = 4u32;
+        let values: Vec<i32> = vec![
+            0, 0, 0, 0,
+            1, -1, 1000, -1000,
+            i32::MAX, i32::MIN, i32::MAX, i32::MIN,
+            i32::MIN, i32::MAX, 0, -1,
+        ];
+
+        let encoded = encode_results_delta(&values, stride).unwrap();
+        let decoded = decode_plain(&encoded);
+
+        assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn zigzag_round_trips_across_the_full_i32_range_edges() {
+        for v in [0, 1, -1, 2, -2, i32::MAX, i32::MIN, i32::MAX - 1, i32::MIN + 1] {
+            assert_eq!(zigzag_decode(zigzag_encode(v)), v, "zigzag round-trip failed for {v}");
+        }
+    }
+}